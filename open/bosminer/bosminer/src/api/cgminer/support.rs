@@ -22,6 +22,9 @@
 
 //! Defines support structures for API responses serialization
 
+pub mod batch;
+pub mod transport;
+
 use super::response;
 use super::TIMESTAMP;
 
@@ -0,0 +1,197 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Parses and dispatches batched cgminer API commands (`summary+pools+devs`) into a single
+//! aggregate `MultiResponse`, so a monitor can fetch several reports in one round trip.
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json as json;
+
+use super::{MultiResponse, ResponseType};
+
+/// A single command extracted from a (possibly batched) request
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    pub name: String,
+    pub parameter: Option<String>,
+}
+
+impl Command {
+    fn parse_one(raw: &str) -> Self {
+        match raw.find(',') {
+            Some(idx) => Command {
+                name: raw[..idx].to_string(),
+                parameter: Some(raw[idx + 1..].to_string()),
+            },
+            None => Command {
+                name: raw.to_string(),
+                parameter: None,
+            },
+        }
+    }
+}
+
+/// A single entry of the JSON-RPC-2.0-style batch syntax
+#[derive(Deserialize)]
+struct JsonCommand {
+    command: String,
+    parameter: Option<String>,
+}
+
+/// Parses a batch request, accepting either cgminer's `cmd1+cmd2` / `cmd1|cmd2` pipe/plus
+/// syntax or a JSON array of `{"command": ..., "parameter": ...}` objects. A plain, single
+/// command parses to a one-element result either way.
+pub fn parse_commands(input: &str) -> Vec<Command> {
+    let trimmed = input.trim();
+
+    if trimmed.starts_with('[') {
+        if let Ok(commands) = json::from_str::<Vec<JsonCommand>>(trimmed) {
+            return commands
+                .into_iter()
+                .map(|command| Command {
+                    name: command.command,
+                    parameter: command.parameter,
+                })
+                .collect();
+        }
+    }
+
+    trimmed
+        .split(|c| c == '+' || c == '|')
+        .map(Command::parse_one)
+        .collect()
+}
+
+/// Dispatches a single, already parsed `Command` to its `ResponseType::Single`
+pub type SingleDispatch<'a> = dyn Fn(&Command) -> BoxFuture<'a, ResponseType> + Send + Sync + 'a;
+
+/// Runs every command in `input` through `dispatch` independently and aggregates the
+/// results into a `MultiResponse`. A single command still yields `ResponseType::Single`, as
+/// cgminer clients expect. One failing sub-command never aborts the batch: each entry keeps
+/// its own `STATUS` block exactly as `dispatch` produced it, success or error alike.
+pub async fn dispatch_batch(input: &str, dispatch: &SingleDispatch<'_>) -> ResponseType {
+    let commands = parse_commands(input);
+
+    if let [only] = commands.as_slice() {
+        return dispatch(only).await;
+    }
+
+    let mut multi = MultiResponse::new();
+    for command in &commands {
+        let response = dispatch(command).await;
+        let value = json::to_value(&response).expect("Response serialization failed");
+        multi.add_response(&command.name.to_uppercase(), value);
+    }
+    ResponseType::Multi(multi)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_commands_splits_plus_and_pipe_syntax() {
+        let expected = vec![
+            Command {
+                name: "summary".to_string(),
+                parameter: None,
+            },
+            Command {
+                name: "pools".to_string(),
+                parameter: None,
+            },
+        ];
+        assert_eq!(parse_commands("summary+pools"), expected);
+        assert_eq!(parse_commands("summary|pools"), expected);
+    }
+
+    #[test]
+    fn parse_commands_extracts_a_comma_separated_parameter() {
+        assert_eq!(
+            parse_commands("pools,0"),
+            vec![Command {
+                name: "pools".to_string(),
+                parameter: Some("0".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_commands_accepts_the_json_rpc_2_0_style_array_syntax() {
+        let input = r#"[{"command":"pools","parameter":"0"},{"command":"summary"}]"#;
+        assert_eq!(
+            parse_commands(input),
+            vec![
+                Command {
+                    name: "pools".to_string(),
+                    parameter: Some("0".to_string()),
+                },
+                Command {
+                    name: "summary".to_string(),
+                    parameter: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_commands_treats_a_single_plain_command_as_one_element() {
+        assert_eq!(
+            parse_commands("summary"),
+            vec![Command {
+                name: "summary".to_string(),
+                parameter: None,
+            }]
+        );
+    }
+
+    /// Stands in for a real command handler: tags its response with the command name it was
+    /// invoked with, so aggregation behavior can be observed without depending on the
+    /// concrete `Response` type's construction.
+    fn marker_dispatch(command: &Command) -> BoxFuture<'static, ResponseType> {
+        let name = command.name.clone();
+        Box::pin(async move {
+            let mut multi = MultiResponse::new();
+            multi.add_response("marker", json::json!(name));
+            ResponseType::Multi(multi)
+        })
+    }
+
+    #[tokio::test]
+    async fn dispatch_batch_passes_a_single_command_straight_through_unwrapped() {
+        let response = dispatch_batch("summary", &marker_dispatch).await;
+        let value = json::to_value(&response).expect("serialization failed");
+
+        // a single command's response must not be nested under its own command name
+        assert_eq!(value["marker"][0], json::json!("summary"));
+    }
+
+    #[tokio::test]
+    async fn dispatch_batch_aggregates_multiple_commands_under_their_uppercased_names() {
+        let response = dispatch_batch("summary+pools", &marker_dispatch).await;
+        let value = json::to_value(&response).expect("serialization failed");
+
+        assert_eq!(value["SUMMARY"][0]["marker"][0], json::json!("summary"));
+        assert_eq!(value["POOLS"][0]["marker"][0], json::json!("pools"));
+    }
+}
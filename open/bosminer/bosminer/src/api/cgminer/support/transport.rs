@@ -0,0 +1,164 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! TCP and Unix-domain-socket transports for the cgminer-compatible API, so management
+//! tooling on the same host can talk to it without going through the network stack.
+
+use std::fmt;
+use std::fs::Permissions;
+use std::net::SocketAddr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, UnixListener};
+
+use super::ResponseType;
+
+/// Upper bound on a single command frame, so a connection that never sends the `\0`
+/// terminator can't grow the read buffer without bound
+const MAX_FRAME_LEN: usize = 4096;
+
+/// Permissions applied to a freshly bound IPC socket: owner read/write only, since the
+/// socket grants the same access as the TCP port would, without the network exposure
+const IPC_SOCKET_MODE: u32 = 0o600;
+
+/// Where the API listens. Parsed from e.g. `tcp://127.0.0.1:4028` or
+/// `ipc:///var/run/bosminer/api.sock`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListenerAddress {
+    Tcp(SocketAddr),
+    Ipc(PathBuf),
+}
+
+impl FromStr for ListenerAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(rest) = s.strip_prefix("tcp://") {
+            rest.parse()
+                .map(ListenerAddress::Tcp)
+                .map_err(|e| format!("invalid TCP listener address '{}': {}", rest, e))
+        } else if let Some(rest) = s.strip_prefix("ipc://") {
+            Ok(ListenerAddress::Ipc(PathBuf::from(rest)))
+        } else {
+            Err(format!(
+                "listener address must start with 'tcp://' or 'ipc://', got '{}'",
+                s
+            ))
+        }
+    }
+}
+
+impl fmt::Display for ListenerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ListenerAddress::Tcp(addr) => write!(f, "tcp://{}", addr),
+            ListenerAddress::Ipc(path) => write!(f, "ipc://{}", path.display()),
+        }
+    }
+}
+
+/// Dispatches a single, already decoded command frame to a `ResponseType`
+pub type Dispatch = Arc<dyn Fn(String) -> BoxFuture<'static, ResponseType> + Send + Sync>;
+
+/// Reads a command frame terminated by `\0` (the cgminer API convention) from `socket`,
+/// runs it through `dispatch` and writes the serialized `ResponseType` back, also
+/// `\0`-terminated. Bails out (dropping the connection) if the frame exceeds
+/// `MAX_FRAME_LEN`, terminator or not: the length is checked before a found terminator can
+/// ever short-circuit the loop, not just on the no-terminator-yet path.
+async fn handle_connection<S>(socket: S, dispatch: &Dispatch)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut reader = BufReader::new(socket);
+    let mut buf = Vec::new();
+
+    loop {
+        let available = match reader.fill_buf().await {
+            Ok(available) => available,
+            Err(_) => return,
+        };
+        if available.is_empty() {
+            // EOF before a terminator ever arrived
+            return;
+        }
+
+        match available.iter().position(|&byte| byte == 0) {
+            Some(pos) => {
+                if buf.len() + pos > MAX_FRAME_LEN {
+                    return;
+                }
+                buf.extend_from_slice(&available[..pos]);
+                reader.consume(pos + 1);
+                break;
+            }
+            None => {
+                buf.extend_from_slice(available);
+                let consumed = available.len();
+                reader.consume(consumed);
+
+                if buf.len() > MAX_FRAME_LEN {
+                    return;
+                }
+            }
+        }
+    }
+
+    let command = String::from_utf8_lossy(&buf).into_owned();
+    let response = dispatch(command).await;
+
+    let mut serialized = match serde_json::to_vec(&response) {
+        Ok(bytes) => bytes,
+        Err(_) => return,
+    };
+    serialized.push(0);
+    let _ = reader.into_inner().write_all(&serialized).await;
+}
+
+/// Serves the cgminer API on `address`, handing every connection's command frame to
+/// `dispatch`. Runs until accepting a connection fails, and returns that error.
+pub async fn serve(address: ListenerAddress, dispatch: Dispatch) -> std::io::Result<()> {
+    match address {
+        ListenerAddress::Tcp(addr) => {
+            let listener = TcpListener::bind(addr).await?;
+            loop {
+                let (socket, _) = listener.accept().await?;
+                let dispatch = dispatch.clone();
+                tokio::spawn(async move { handle_connection(socket, &dispatch).await });
+            }
+        }
+        ListenerAddress::Ipc(path) => {
+            let _ = std::fs::remove_file(&path);
+            let listener = UnixListener::bind(&path)?;
+            std::fs::set_permissions(&path, Permissions::from_mode(IPC_SOCKET_MODE))?;
+            loop {
+                let (socket, _) = listener.accept().await?;
+                let dispatch = dispatch.clone();
+                tokio::spawn(async move { handle_connection(socket, &dispatch).await });
+            }
+        }
+    }
+}
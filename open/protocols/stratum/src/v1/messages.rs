@@ -10,11 +10,16 @@ use super::framing;
 use super::{ExtraNonce1, V1Handler, V1Protocol};
 use crate::error::{Error, Result};
 use crate::v1::framing::Method;
-use crate::v1::{HexBytes, HexU32Le};
+use crate::v1::HexBytes;
 
 #[cfg(test)]
 pub mod test;
 
+pub mod rpc;
+pub mod serde_hex;
+
+use serde_hex::{BigEndian, FixedHex, LittleEndian};
+
 macro_rules! impl_conversion_request {
     ($request:ty, $method:path, $handler_fn:ident) => {
         impl TryFrom<$request> for framing::RequestPayload {
@@ -180,6 +185,126 @@ impl Authorize {
 
 impl_conversion_request!(Authorize, Method::Authorize, visit_authorize);
 
+/// `mining.configure` request: the list of requested extensions plus an extension-specific
+/// parameter map. Only the `version-rolling` (ASICBoost) extension is currently understood.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Configure(pub Vec<String>, pub serde_json::Map<String, serde_json::Value>);
+
+impl Configure {
+    pub fn new(extensions: Vec<String>, params: serde_json::Map<String, serde_json::Value>) -> Self {
+        Self(extensions, params)
+    }
+
+    pub fn extensions(&self) -> &Vec<String> {
+        &self.0
+    }
+
+    /// The `version-rolling.mask` parameter, if present: the bits the client may flip in
+    /// the block header version
+    pub fn version_rolling_mask(&self) -> Option<u32> {
+        self.1
+            .get("version-rolling.mask")
+            .and_then(|value| value.as_str())
+            .and_then(|mask| u32::from_str_radix(mask, 16).ok())
+    }
+
+    /// The `version-rolling.min-bit-count` parameter, if present
+    pub fn version_rolling_min_bit_count(&self) -> Option<u32> {
+        self.1
+            .get("version-rolling.min-bit-count")
+            .and_then(|value| value.as_u64())
+            .map(|count| count as u32)
+    }
+}
+
+impl_conversion_request!(Configure, Method::Configure, visit_configure);
+
+/// `mining.configure` response: per-extension negotiation outcome
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ConfigureResult(pub serde_json::Map<String, serde_json::Value>);
+
+impl ConfigureResult {
+    /// Builds a response accepting `version-rolling` with the given negotiated `mask`
+    pub fn with_version_rolling_mask(mask: u32) -> Self {
+        let mut params = serde_json::Map::new();
+        params.insert("version-rolling".into(), true.into());
+        params.insert("version-rolling.mask".into(), format!("{:08x}", mask).into());
+        Self(params)
+    }
+
+    pub fn version_rolling(&self) -> bool {
+        self.0
+            .get("version-rolling")
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false)
+    }
+
+    pub fn version_rolling_mask(&self) -> Option<u32> {
+        self.0
+            .get("version-rolling.mask")
+            .and_then(|value| value.as_str())
+            .and_then(|mask| u32::from_str_radix(mask, 16).ok())
+    }
+}
+
+impl_conversion_response!(ConfigureResult);
+
+// Named `configure_test` rather than `test` to avoid colliding with the `pub mod test;`
+// declaration above.
+#[cfg(test)]
+mod configure_test {
+    use super::*;
+
+    fn configure_with(params: serde_json::Map<String, serde_json::Value>) -> Configure {
+        Configure::new(vec!["version-rolling".to_string()], params)
+    }
+
+    #[test]
+    fn version_rolling_mask_parses_a_hex_string() {
+        let mut params = serde_json::Map::new();
+        params.insert("version-rolling.mask".into(), "1fffe000".into());
+
+        assert_eq!(configure_with(params).version_rolling_mask(), Some(0x1fff_e000));
+    }
+
+    #[test]
+    fn version_rolling_mask_rejects_a_0x_prefixed_value() {
+        let mut params = serde_json::Map::new();
+        params.insert("version-rolling.mask".into(), "0x1fffe000".into());
+
+        assert_eq!(configure_with(params).version_rolling_mask(), None);
+    }
+
+    #[test]
+    fn version_rolling_mask_is_none_when_absent() {
+        assert_eq!(configure_with(serde_json::Map::new()).version_rolling_mask(), None);
+    }
+
+    #[test]
+    fn version_rolling_min_bit_count_reads_the_parameter() {
+        let mut params = serde_json::Map::new();
+        params.insert("version-rolling.min-bit-count".into(), 2.into());
+
+        assert_eq!(configure_with(params).version_rolling_min_bit_count(), Some(2));
+    }
+
+    #[test]
+    fn with_version_rolling_mask_builds_an_accepting_result() {
+        let result = ConfigureResult::with_version_rolling_mask(0x1fff_e000);
+
+        assert!(result.version_rolling());
+        assert_eq!(result.version_rolling_mask(), Some(0x1fff_e000));
+    }
+
+    #[test]
+    fn version_rolling_defaults_to_false_when_absent() {
+        let result = ConfigureResult(serde_json::Map::new());
+
+        assert!(!result.version_rolling());
+        assert_eq!(result.version_rolling_mask(), None);
+    }
+}
+
 /// Difficulty value set by the upstream stratum server
 /// Note, that we explicitly enforce 1 one element array so that serde doesn't flatten the
 /// 'params' JSON array to a single value, eliminating the array completely.
@@ -205,34 +330,80 @@ impl JobId {
     pub fn from_slice(job_id: &[u8]) -> Self {
         Self(HexBytes(Vec::from(job_id)))
     }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &(self.0).0
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
-pub struct PrevHash(HexBytes);
+pub struct PrevHash(FixedHex<BigEndian, 32>);
+
+impl PrevHash {
+    pub fn new(prev_hash: [u8; 32]) -> Self {
+        Self(FixedHex::new(prev_hash))
+    }
+}
 
 /// Leading part of the coinbase transaction
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct CoinBase1(HexBytes);
 
+impl CoinBase1 {
+    pub fn new(coin_base_1: Vec<u8>) -> Self {
+        Self(HexBytes(coin_base_1))
+    }
+}
+
 /// Trailing part of the coinbase transaction
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct CoinBase2(HexBytes);
 
+impl CoinBase2 {
+    pub fn new(coin_base_2: Vec<u8>) -> Self {
+        Self(HexBytes(coin_base_2))
+    }
+}
+
 /// Merkle branch of transaction hashes leading to coinbase
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct MerkleBranch(Vec<HexBytes>);
 
+impl MerkleBranch {
+    pub fn new(merkle_branch: Vec<HexBytes>) -> Self {
+        Self(merkle_branch)
+    }
+}
+
 /// Version field of Bitcoin block header
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
-pub struct Version(HexU32Le);
+pub struct Version(FixedHex<LittleEndian, 4>);
+
+impl Version {
+    pub fn new(version: u32) -> Self {
+        Self(FixedHex::new(version.to_be_bytes()))
+    }
+}
 
 /// Network difficulty target
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
-pub struct Bits(HexU32Le);
+pub struct Bits(FixedHex<LittleEndian, 4>);
+
+impl Bits {
+    pub fn new(bits: u32) -> Self {
+        Self(FixedHex::new(bits.to_be_bytes()))
+    }
+}
 
 /// Network time
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
-pub struct Time(HexU32Le);
+pub struct Time(FixedHex<LittleEndian, 4>);
+
+impl Time {
+    pub fn new(time: u32) -> Self {
+        Self(FixedHex::new(time.to_be_bytes()))
+    }
+}
 
 /// New mining job notification
 /// TODO generate the field accessors
@@ -251,12 +422,37 @@ pub struct Notify(
 
 // TODO consider making the attributes return new type references, it would be less prone to typos
 impl Notify {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        job_id: JobId,
+        prev_hash: PrevHash,
+        coin_base_1: CoinBase1,
+        coin_base_2: CoinBase2,
+        merkle_branch: MerkleBranch,
+        version: Version,
+        bits: Bits,
+        time: Time,
+        clean_jobs: bool,
+    ) -> Self {
+        Self(
+            job_id,
+            prev_hash,
+            coin_base_1,
+            coin_base_2,
+            merkle_branch,
+            version,
+            bits,
+            time,
+            clean_jobs,
+        )
+    }
+
     pub fn job_id(&self) -> &[u8] {
         &((self.0).0).0
     }
 
     pub fn prev_hash(&self) -> &[u8] {
-        &((self.1).0).0
+        (self.1).0.as_bytes()
     }
 
     pub fn coin_base_1(&self) -> &[u8] {
@@ -272,15 +468,15 @@ impl Notify {
     }
 
     pub fn version(&self) -> u32 {
-        ((self.5).0).0
+        u32::from_be_bytes((self.5).0.as_bytes().try_into().expect("BUG: Version is 4 bytes"))
     }
 
     pub fn bits(&self) -> u32 {
-        ((self.6).0).0
+        u32::from_be_bytes((self.6).0.as_bytes().try_into().expect("BUG: Bits is 4 bytes"))
     }
 
     pub fn time(&self) -> u32 {
-        ((self.7).0).0
+        u32::from_be_bytes((self.7).0.as_bytes().try_into().expect("BUG: Time is 4 bytes"))
     }
 
     pub fn clean_jobs(&self) -> bool {
@@ -294,13 +490,33 @@ impl_conversion_request!(Notify, Method::Notify, visit_notify);
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct UserName(String);
 
-/// Extra nonce 2, note the underlying serialization type
+impl UserName {
+    pub fn new(user_name: String) -> Self {
+        Self(user_name)
+    }
+}
+
+/// Extra nonce 2, note the underlying serialization type. Its length is negotiated via
+/// `mining.subscribe`'s `extra_nonce_2_size` rather than fixed by the protocol, so unlike
+/// `Nonce`/`Version`/`Bits`/`Time` it cannot use a const-generic `FixedHex` length.
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct ExtraNonce2(HexBytes);
 
+impl ExtraNonce2 {
+    pub fn new(extra_nonce_2: Vec<u8>) -> Self {
+        Self(HexBytes(extra_nonce_2))
+    }
+}
+
 /// Nonce for the block
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
-pub struct Nonce(HexU32Le);
+pub struct Nonce(FixedHex<LittleEndian, 4>);
+
+impl Nonce {
+    pub fn new(nonce: u32) -> Self {
+        Self(FixedHex::new(nonce.to_be_bytes()))
+    }
+}
 
 /// New mining job notification
 /// TODO generate the field accessors
@@ -308,6 +524,18 @@ pub struct Nonce(HexU32Le);
 pub struct Submit(UserName, JobId, ExtraNonce2, Time, Nonce, Version);
 
 impl Submit {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        user_name: UserName,
+        job_id: JobId,
+        extra_nonce_2: ExtraNonce2,
+        time: Time,
+        nonce: Nonce,
+        version: Version,
+    ) -> Self {
+        Self(user_name, job_id, extra_nonce_2, time, nonce, version)
+    }
+
     pub fn user_name(&self) -> &String {
         &(self.0).0
     }
@@ -321,15 +549,34 @@ impl Submit {
     }
 
     pub fn time(&self) -> u32 {
-        ((self.3).0).0
+        u32::from_be_bytes((self.3).0.as_bytes().try_into().expect("BUG: Time is 4 bytes"))
     }
 
     pub fn nonce(&self) -> u32 {
-        ((self.4).0).0
+        u32::from_be_bytes((self.4).0.as_bytes().try_into().expect("BUG: Nonce is 4 bytes"))
     }
 
     pub fn version(&self) -> u32 {
-        ((self.5).0).0
+        u32::from_be_bytes((self.5).0.as_bytes().try_into().expect("BUG: Version is 4 bytes"))
+    }
+
+    /// Checks that this share's header version only differs from the job's `notify_version`
+    /// within the negotiated version-rolling `mask`, rejecting rolls of bits the client
+    /// never negotiated for.
+    pub fn validate_version_roll(&self, notify_version: u32, mask: u32) -> Result<()> {
+        let rolled_bits = self.version() ^ notify_version;
+        if rolled_bits & !mask != 0 {
+            Err(ErrorKind::Json(format!(
+                "submitted version 0x{:08x} rolls bits outside the negotiated mask 0x{:08x} \
+                 (job version 0x{:08x})",
+                self.version(),
+                mask,
+                notify_version
+            ))
+            .into())
+        } else {
+            Ok(())
+        }
     }
 }
 
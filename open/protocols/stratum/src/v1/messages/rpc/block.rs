@@ -0,0 +1,84 @@
+//! Reconstructs a full Bitcoin block from an accepted `Submit` share and relays it to Bitcoin
+//! Core via `submitblock`
+
+use bitcoin_hashes::sha256d::Hash as DHash;
+use bitcoin_hashes::Hash as HashTrait;
+
+use super::coinbase;
+use super::job::JobContext;
+use super::json::{SubmitBlockRequest, SubmitBlockResult};
+use super::push_var_int;
+use crate::v1::messages::Submit;
+
+/// Reconstructs the coinbase transaction and folds the merkle root for `submit` against
+/// `ctx`, the job context stashed by `JobBuilder::build_notify` when the job was handed out.
+/// The merkle tree's leaves are txids, never wtxids, so the coinbase's *legacy* txid is used
+/// here even when it was BIP144-serialized with a witness commitment (see
+/// `coinbase::legacy_txid`).
+fn merkle_root(ctx: &JobContext, coinbase: &[u8]) -> [u8; 32] {
+    let mut root = coinbase::legacy_txid(coinbase, ctx.has_witness_commitment);
+    for branch_hash in &ctx.merkle_branch {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(&root);
+        buf.extend_from_slice(branch_hash);
+        root = DHash::hash(&buf).into_inner();
+    }
+    root
+}
+
+/// Assembles the serialized block for `submit`, ready to be sent to Bitcoin Core's
+/// `submitblock`. `extranonce1` is the pool-assigned prefix; `version` is the rolled header
+/// version to use (see `mining.configure`'s version-rolling negotiation).
+pub fn reconstruct_block(ctx: &JobContext, submit: &Submit, extranonce1: &[u8], version: u32) -> Vec<u8> {
+    let mut coinbase = Vec::with_capacity(
+        ctx.coin_base_1.len() + extranonce1.len() + submit.extra_nonce_2().len() + ctx.coin_base_2.len(),
+    );
+    coinbase.extend_from_slice(&ctx.coin_base_1);
+    coinbase.extend_from_slice(extranonce1);
+    coinbase.extend_from_slice(submit.extra_nonce_2());
+    coinbase.extend_from_slice(&ctx.coin_base_2);
+
+    let merkle_root = merkle_root(ctx, &coinbase);
+
+    let mut block = Vec::new();
+    block.extend_from_slice(&version.to_le_bytes());
+    block.extend_from_slice(&ctx.prev_hash);
+    block.extend_from_slice(&merkle_root);
+    block.extend_from_slice(&submit.time().to_le_bytes());
+    block.extend_from_slice(&ctx.bits.to_le_bytes());
+    block.extend_from_slice(&submit.nonce().to_le_bytes());
+
+    push_var_int(&mut block, 1 + ctx.transactions.len() as u64);
+    block.extend_from_slice(&coinbase);
+    for tx in &ctx.transactions {
+        block.extend_from_slice(tx);
+    }
+
+    block
+}
+
+/// Builds the `submitblock` request for `block`
+pub fn submit_block_request(block: &[u8]) -> SubmitBlockRequest {
+    SubmitBlockRequest(hex::encode(block))
+}
+
+/// Human readable outcome derived from Bitcoin Core's `submitblock` result, so that callers
+/// can surface why a block was rejected without reaching into the raw RPC response.
+///
+/// This intentionally does not reuse `response::Error`/`StatusCode`: those types live in the
+/// bosminer crate's cgminer API layer, while this crate (`stratum`) sits below it and has no
+/// dependency on it. A caller in that layer is expected to map this outcome onto its own
+/// response types at the boundary.
+pub enum BlockSubmitOutcome {
+    Accepted,
+    Rejected(String),
+}
+
+impl From<SubmitBlockResult> for BlockSubmitOutcome {
+    fn from(result: SubmitBlockResult) -> Self {
+        match result.reject_reason() {
+            None => BlockSubmitOutcome::Accepted,
+            Some(reason) => BlockSubmitOutcome::Rejected(reason.to_string()),
+        }
+    }
+}
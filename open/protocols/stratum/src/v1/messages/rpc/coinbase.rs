@@ -0,0 +1,225 @@
+//! Builds the coinbase transaction for a locally generated job, reserving a placeholder
+//! region in the scriptSig for the miner's extranonce1/extranonce2.
+
+use bitcoin_hashes::sha256d::Hash as DHash;
+use bitcoin_hashes::Hash as HashTrait;
+
+use super::push_var_int;
+
+/// Length in bytes of the BIP141 witness data appended to the coinbase input when a witness
+/// commitment output is present: one witness stack (count=1), one item (length=32), followed
+/// by the all-zero 32-byte witness reserved value.
+const WITNESS_RESERVED_VALUE_STACK_LEN: usize = 1 + 1 + 32;
+
+/// Encodes `height` as a minimal-length little-endian push, per BIP34
+fn push_height(buf: &mut Vec<u8>, height: u64) {
+    let mut bytes = height.to_le_bytes().to_vec();
+    while bytes.last() == Some(&0) && bytes.len() > 1 {
+        bytes.pop();
+    }
+    if bytes.last().copied().unwrap_or(0) & 0x80 != 0 {
+        bytes.push(0);
+    }
+    buf.push(bytes.len() as u8);
+    buf.extend_from_slice(&bytes);
+}
+
+/// Result of building a coinbase transaction: the raw bytes split around the extranonce
+/// placeholder region that the miner is expected to fill in
+pub struct CoinBaseParts {
+    /// Everything up to (and including) the start of the extranonce placeholder
+    pub coin_base_1: Vec<u8>,
+    /// Everything after the extranonce placeholder
+    pub coin_base_2: Vec<u8>,
+}
+
+/// Builds a coinbase transaction paying `coinbase_value` satoshi to `script_pubkey`, with a
+/// `extranonce_size` byte placeholder reserved in the scriptSig right after the BIP34 height
+/// push and an arbitrary signature-script tag. When `witness_commitment_script` is given (the
+/// `scriptPubKey` of `getblocktemplate`'s `default_witnesscommitment`), the coinbase is
+/// BIP144-serialized with a second output carrying that commitment and the mandatory all-zero
+/// witness reserved value on the input, as required whenever the block contains a segwit
+/// transaction.
+pub fn build(
+    height: u64,
+    coinbase_value: u64,
+    script_pubkey: &[u8],
+    extranonce_size: usize,
+    witness_commitment_script: Option<&[u8]>,
+) -> CoinBaseParts {
+    let mut script_sig = Vec::new();
+    push_height(&mut script_sig, height);
+    script_sig.extend_from_slice(b"/braiins/");
+
+    let mut coin_base_1 = Vec::new();
+    // version
+    coin_base_1.extend_from_slice(&1u32.to_le_bytes());
+    if witness_commitment_script.is_some() {
+        // segwit marker + flag (BIP144)
+        coin_base_1.extend_from_slice(&[0x00, 0x01]);
+    }
+    // single input
+    push_var_int(&mut coin_base_1, 1);
+    // null previous outpoint
+    coin_base_1.extend_from_slice(&[0u8; 32]);
+    coin_base_1.extend_from_slice(&0xffff_ffffu32.to_le_bytes());
+    // scriptSig length covers the fixed prefix, the extranonce placeholder and nothing else
+    push_var_int(
+        &mut coin_base_1,
+        (script_sig.len() + extranonce_size) as u64,
+    );
+    coin_base_1.extend_from_slice(&script_sig);
+
+    let mut coin_base_2 = Vec::new();
+    // sequence
+    coin_base_2.extend_from_slice(&0xffff_ffffu32.to_le_bytes());
+    // output paying the full coinbase value to the pool's script, plus the witness
+    // commitment output when one was given
+    push_var_int(&mut coin_base_2, if witness_commitment_script.is_some() { 2 } else { 1 });
+    coin_base_2.extend_from_slice(&coinbase_value.to_le_bytes());
+    push_var_int(&mut coin_base_2, script_pubkey.len() as u64);
+    coin_base_2.extend_from_slice(script_pubkey);
+    if let Some(commitment_script) = witness_commitment_script {
+        // the witness commitment output always carries zero value
+        coin_base_2.extend_from_slice(&0u64.to_le_bytes());
+        push_var_int(&mut coin_base_2, commitment_script.len() as u64);
+        coin_base_2.extend_from_slice(commitment_script);
+
+        // BIP141 witness reserved value: a single all-zero 32-byte stack item on the
+        // coinbase's only input
+        push_var_int(&mut coin_base_2, 1);
+        push_var_int(&mut coin_base_2, 32);
+        coin_base_2.extend_from_slice(&[0u8; 32]);
+    }
+    // locktime
+    coin_base_2.extend_from_slice(&0u32.to_le_bytes());
+
+    CoinBaseParts {
+        coin_base_1,
+        coin_base_2,
+    }
+}
+
+/// Computes the coinbase's *txid* (its non-witness double-SHA256), as needed for the block's
+/// tx merkle root: BIP141 defines the merkle-tree leaf for every transaction, coinbase
+/// included, as its txid, never its wtxid. `assembled` is the fully assembled coinbase
+/// (`coin_base_1` + extranonce + `coin_base_2`, in that order); `has_witness_commitment` must
+/// match whatever was passed to `build` when the parts were generated.
+pub fn legacy_txid(assembled: &[u8], has_witness_commitment: bool) -> [u8; 32] {
+    if !has_witness_commitment {
+        return DHash::hash(assembled).into_inner();
+    }
+
+    let len = assembled.len();
+    let mut legacy = Vec::with_capacity(len - 2 - WITNESS_RESERVED_VALUE_STACK_LEN);
+    // version, then skip the 2-byte segwit marker+flag
+    legacy.extend_from_slice(&assembled[..4]);
+    // vin, vout: everything up to the witness stack and locktime
+    legacy.extend_from_slice(&assembled[6..len - 4 - WITNESS_RESERVED_VALUE_STACK_LEN]);
+    // locktime, skipping the witness stack right before it
+    legacy.extend_from_slice(&assembled[len - 4..]);
+
+    DHash::hash(&legacy).into_inner()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push_height_uses_minimal_little_endian_encoding() {
+        let mut buf = Vec::new();
+        push_height(&mut buf, 1);
+        assert_eq!(buf, vec![1, 1]);
+
+        let mut buf = Vec::new();
+        push_height(&mut buf, 0x0203);
+        assert_eq!(buf, vec![2, 0x03, 0x02]);
+    }
+
+    #[test]
+    fn push_height_pads_with_a_zero_byte_when_the_high_bit_is_set() {
+        // 0x80 alone would be read back as a negative script number without the padding byte
+        let mut buf = Vec::new();
+        push_height(&mut buf, 0x80);
+        assert_eq!(buf, vec![2, 0x80, 0x00]);
+    }
+
+    #[test]
+    fn build_reserves_an_extranonce_placeholder_between_coin_base_1_and_2() {
+        let extranonce_size = 8;
+        let parts = build(42, 5_000_000_000, &[0x76, 0xa9], extranonce_size, None);
+
+        // version(4) + input count varint(1) + null prevout(32) + index(4)
+        let fixed_prefix_len = 4 + 1 + 32 + 4;
+        let script_sig_len_prefix = parts.coin_base_1[fixed_prefix_len] as usize;
+        let script_sig_bytes = &parts.coin_base_1[fixed_prefix_len + 1..];
+
+        // the scriptSig length prefix counts the extranonce placeholder even though it is
+        // not actually present in coin_base_1's bytes: the miner splices it in later
+        assert_eq!(script_sig_len_prefix, script_sig_bytes.len() + extranonce_size);
+    }
+
+    #[test]
+    fn build_without_a_witness_commitment_omits_the_segwit_marker_and_flag() {
+        let parts = build(42, 5_000_000_000, &[0x76, 0xa9], 8, None);
+        // version(4) is immediately followed by the input count varint, not 0x00 0x01
+        assert_ne!(&parts.coin_base_1[4..6], &[0x00, 0x01]);
+    }
+
+    #[test]
+    fn build_with_a_witness_commitment_adds_the_marker_flag_output_and_reserved_value() {
+        let pool_script = [0x76, 0xa9];
+        let commitment_script = vec![0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+        let parts = build(42, 5_000_000_000, &pool_script, 8, Some(&commitment_script));
+
+        assert_eq!(&parts.coin_base_1[4..6], &[0x00, 0x01]);
+
+        // sequence(4) + output count varint(1, value 2) + first output (value 8 + script len
+        // varint 1 + script)
+        let first_output_len = 8 + 1 + pool_script.len();
+        let commitment_offset = 4 + 1 + first_output_len;
+        let second_output = &parts.coin_base_2[commitment_offset..];
+
+        // the witness commitment output carries zero value
+        assert_eq!(&second_output[..8], &[0u8; 8]);
+        assert_eq!(second_output[8] as usize, commitment_script.len());
+        assert_eq!(&second_output[9..9 + commitment_script.len()], &commitment_script[..]);
+
+        // witness stack (count=1, len=32, 32 zero bytes) sits right before the 4-byte locktime
+        let witness_start = parts.coin_base_2.len() - 4 - WITNESS_RESERVED_VALUE_STACK_LEN;
+        assert_eq!(parts.coin_base_2[witness_start], 1); // one witness item
+        assert_eq!(parts.coin_base_2[witness_start + 1], 32); // 32 bytes long
+        assert_eq!(&parts.coin_base_2[witness_start + 2..witness_start + 2 + 32], &[0u8; 32]);
+    }
+
+    #[test]
+    fn legacy_txid_without_a_witness_commitment_hashes_the_bytes_as_is() {
+        let parts = build(42, 5_000_000_000, &[0x76, 0xa9], 0, None);
+        let assembled = [parts.coin_base_1.clone(), parts.coin_base_2.clone()].concat();
+
+        assert_eq!(legacy_txid(&assembled, false), DHash::hash(&assembled).into_inner());
+    }
+
+    #[test]
+    fn legacy_txid_strips_the_segwit_marker_flag_and_witness_reserved_value() {
+        let commitment_script = vec![0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+        let witness_parts = build(42, 5_000_000_000, &[0x76, 0xa9], 0, Some(&commitment_script));
+        let assembled_witness = [witness_parts.coin_base_1.clone(), witness_parts.coin_base_2.clone()].concat();
+
+        // rebuild the expected non-witness serialization by hand: version, then vin/vout
+        // (skipping the 2-byte marker+flag), then locktime (skipping the witness stack)
+        let mut expected_legacy = Vec::new();
+        expected_legacy.extend_from_slice(&assembled_witness[..4]);
+        let witness_trailer = 4 + WITNESS_RESERVED_VALUE_STACK_LEN;
+        expected_legacy.extend_from_slice(&assembled_witness[6..assembled_witness.len() - witness_trailer]);
+        expected_legacy.extend_from_slice(&assembled_witness[assembled_witness.len() - 4..]);
+
+        assert_eq!(
+            legacy_txid(&assembled_witness, true),
+            DHash::hash(&expected_legacy).into_inner()
+        );
+        // sanity: the legacy txid must differ from naively hashing the witness-serialized bytes
+        assert_ne!(legacy_txid(&assembled_witness, true), DHash::hash(&assembled_witness).into_inner());
+    }
+}
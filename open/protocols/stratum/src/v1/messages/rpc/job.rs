@@ -0,0 +1,478 @@
+//! Turns a `getblocktemplate` result into a Stratum V1 `Notify`
+
+use std::collections::{HashMap, VecDeque};
+
+use bitcoin_hashes::sha256d::Hash as DHash;
+use bitcoin_hashes::Hash as HashTrait;
+
+use super::coinbase;
+use super::json::GetBlockTemplateResult;
+use crate::error::Result;
+use crate::v1::error::ErrorKind;
+use crate::v1::messages::{
+    Bits, CoinBase1, CoinBase2, ExtraNonce2, JobId, MerkleBranch, Nonce, Notify, PrevHash, Submit, Time, UserName,
+    Version,
+};
+use crate::v1::HexBytes;
+
+/// Upper bound on how many job contexts are kept around for share validation/block
+/// reconstruction at once, so a long-running server can't accumulate them without bound
+const MAX_OUTSTANDING_JOBS: usize = 8;
+
+/// Everything `rpc::block` needs to turn an accepted `Submit` back into a full block
+pub struct JobContext {
+    pub prev_hash: Vec<u8>,
+    pub coin_base_1: Vec<u8>,
+    pub coin_base_2: Vec<u8>,
+    pub merkle_branch: Vec<[u8; 32]>,
+    pub bits: u32,
+    /// The header version handed out in this job's `Notify`, needed to validate a `Submit`'s
+    /// version roll against the negotiated mask
+    pub version: u32,
+    /// Whether the coinbase built for this job carries a BIP141 witness commitment output,
+    /// i.e. whether `coin_base_1`/`coin_base_2` are BIP144-serialized. Needed to compute the
+    /// coinbase's legacy txid for the tx merkle root (see `coinbase::legacy_txid`).
+    pub has_witness_commitment: bool,
+    /// Raw, already serialized non-coinbase transactions, in block order
+    pub transactions: Vec<Vec<u8>>,
+}
+
+/// Computes the merkle branch the miner needs to fold its coinbase hash up to the merkle
+/// root. `row[0]` stands in for the (not yet known) coinbase hash: only its *position* in
+/// each level matters, the value itself is never hashed.
+fn merkle_branch(txids: &[DHash]) -> Vec<HexBytes> {
+    let placeholder = DHash::default();
+    let mut row: Vec<DHash> = std::iter::once(placeholder).chain(txids.iter().copied()).collect();
+    let mut branch = Vec::new();
+
+    while row.len() > 1 {
+        if row.len() % 2 != 0 {
+            let last = *row.last().expect("BUG: row cannot be empty");
+            row.push(last);
+        }
+        branch.push(row[1]);
+
+        let mut next_row = vec![placeholder];
+        let mut i = 2;
+        while i < row.len() {
+            let mut buf = Vec::with_capacity(64);
+            buf.extend_from_slice(&row[i].into_inner());
+            buf.extend_from_slice(&row[i + 1].into_inner());
+            next_row.push(DHash::hash(&buf));
+            i += 2;
+        }
+        row = next_row;
+    }
+
+    branch
+        .into_iter()
+        .map(|hash| HexBytes(hash.into_inner().to_vec()))
+        .collect()
+}
+
+/// Parses a hex string as returned by Bitcoin Core (big-endian display order) into the
+/// internal, reversed byte order used on the wire. `getblocktemplate` is a trust boundary
+/// (a future Core version, a misbehaving proxy, ...), so malformed input is reported rather
+/// than panicking the whole server.
+fn reversed_bytes_from_hex(s: &str) -> Result<Vec<u8>> {
+    let mut bytes = hex::decode(s).map_err(|e| ErrorKind::Json(format!("malformed hex '{}': {}", s, e)))?;
+    bytes.reverse();
+    Ok(bytes)
+}
+
+/// Generates `Notify` messages from successive `getblocktemplate` results, keeping the
+/// extranonce placement and job id counter consistent across calls
+pub struct JobBuilder {
+    extranonce1: Vec<u8>,
+    extranonce2_size: usize,
+    coinbase_script_pubkey: Vec<u8>,
+    last_prev_hash: Option<String>,
+    next_job_id: u64,
+    jobs: HashMap<Vec<u8>, JobContext>,
+    /// Insertion order of `jobs`' keys, oldest first, used to evict once `jobs` grows past
+    /// `MAX_OUTSTANDING_JOBS`
+    job_order: VecDeque<Vec<u8>>,
+    version_rolling_mask: Option<u32>,
+}
+
+impl JobBuilder {
+    pub fn new(extranonce1: Vec<u8>, extranonce2_size: usize, coinbase_script_pubkey: Vec<u8>) -> Self {
+        Self {
+            extranonce1,
+            extranonce2_size,
+            coinbase_script_pubkey,
+            last_prev_hash: None,
+            next_job_id: 0,
+            jobs: HashMap::new(),
+            job_order: VecDeque::new(),
+            version_rolling_mask: None,
+        }
+    }
+
+    /// Looks up the stored context for a job previously handed out via `build_notify`
+    pub fn job_context(&self, job_id: &[u8]) -> Option<&JobContext> {
+        self.jobs.get(job_id)
+    }
+
+    pub fn extranonce1(&self) -> &[u8] {
+        &self.extranonce1
+    }
+
+    /// Records the `version-rolling.mask` negotiated via `mining.configure`, so that job
+    /// generation and share validation agree on which version bits the client may mutate
+    pub fn set_version_rolling_mask(&mut self, mask: Option<u32>) {
+        self.version_rolling_mask = mask;
+    }
+
+    pub fn version_rolling_mask(&self) -> Option<u32> {
+        self.version_rolling_mask
+    }
+
+    /// Validates `submit`'s rolled version against the job it refers to, enforcing the
+    /// negotiated `version_rolling_mask`. A client that never negotiated version-rolling (no
+    /// mask set) is held to the job's version exactly, i.e. a mask of `0`.
+    pub fn validate_submit(&self, submit: &Submit) -> Result<()> {
+        let job = self
+            .jobs
+            .get(submit.job_id())
+            .ok_or_else(|| ErrorKind::Json(format!("unknown job id {:?}", submit.job_id())))?;
+        submit.validate_version_roll(job.version, self.version_rolling_mask.unwrap_or(0))
+    }
+
+    fn alloc_job_id(&mut self) -> JobId {
+        let id = self.next_job_id;
+        self.next_job_id += 1;
+        JobId::from_slice(format!("{:x}", id).as_bytes())
+    }
+
+    /// Builds a `Notify` for `template`, marking `clean_jobs` whenever the previous block
+    /// hash changed since the last call. Fails rather than panicking if `template` (data
+    /// crossing the `getblocktemplate` RPC trust boundary) contains malformed fields.
+    pub fn build_notify(&mut self, template: &GetBlockTemplateResult) -> Result<Notify> {
+        let clean_jobs = self.last_prev_hash.as_deref() != Some(template.previous_block_hash.as_str());
+        self.last_prev_hash = Some(template.previous_block_hash.clone());
+
+        // Jobs built against a stale previous block hash can never be submitted against
+        // successfully again, so drop them along with the rest of the now-obsolete chain tip
+        if clean_jobs {
+            self.jobs.clear();
+            self.job_order.clear();
+        }
+
+        let witness_commitment_script = template
+            .default_witnesscommitment
+            .as_deref()
+            .map(hex::decode)
+            .transpose()
+            .map_err(|e| ErrorKind::Json(format!("malformed default_witnesscommitment: {}", e)))?;
+
+        let parts = coinbase::build(
+            template.height,
+            template.coinbase_value,
+            &self.coinbase_script_pubkey,
+            self.extranonce1.len() + self.extranonce2_size,
+            witness_commitment_script.as_deref(),
+        );
+
+        // The tx merkle tree is built from txids, not wtxids: `getblocktemplate`'s `data` is
+        // the BIP144 (witness) serialization for any segwit transaction, so hashing it
+        // directly would compute the wrong leaf. Bitcoin Core already supplies the txid
+        // alongside `data`, so decode that instead of re-hashing the raw bytes.
+        let mut transactions = Vec::with_capacity(template.transactions.len());
+        let mut txids = Vec::with_capacity(template.transactions.len());
+        for tx in &template.transactions {
+            let data = hex::decode(&tx.data)
+                .map_err(|e| ErrorKind::Json(format!("transaction {} has malformed data: {}", tx.txid, e)))?;
+            let txid_bytes = reversed_bytes_from_hex(&tx.txid)?;
+            let txid = DHash::from_slice(&txid_bytes)
+                .map_err(|e| ErrorKind::Json(format!("transaction txid '{}' is malformed: {}", tx.txid, e)))?;
+            transactions.push(data);
+            txids.push(txid);
+        }
+        let branch = merkle_branch(&txids);
+
+        let bits = u32::from_be_bytes(
+            hex::decode(&template.bits)
+                .map_err(|e| ErrorKind::Json(format!("malformed bits '{}': {}", template.bits, e)))?
+                .try_into()
+                .map_err(|_| ErrorKind::Json(format!("bits '{}' must decode to 4 bytes", template.bits)))?,
+        );
+        let prev_hash: [u8; 32] = reversed_bytes_from_hex(&template.previous_block_hash)?
+            .try_into()
+            .map_err(|_| {
+                ErrorKind::Json(format!(
+                    "previousblockhash '{}' must decode to 32 bytes",
+                    template.previous_block_hash
+                ))
+            })?;
+        let job_id = self.alloc_job_id();
+
+        let job_key = job_id.as_bytes().to_vec();
+        self.jobs.insert(
+            job_key.clone(),
+            JobContext {
+                prev_hash: prev_hash.to_vec(),
+                coin_base_1: parts.coin_base_1.clone(),
+                coin_base_2: parts.coin_base_2.clone(),
+                merkle_branch: branch
+                    .iter()
+                    .map(|hex_bytes| {
+                        let mut buf = [0u8; 32];
+                        buf.copy_from_slice(&hex_bytes.0);
+                        buf
+                    })
+                    .collect(),
+                bits,
+                version: template.version,
+                has_witness_commitment: witness_commitment_script.is_some(),
+                transactions,
+            },
+        );
+        self.job_order.push_back(job_key);
+        while self.job_order.len() > MAX_OUTSTANDING_JOBS {
+            if let Some(oldest) = self.job_order.pop_front() {
+                self.jobs.remove(&oldest);
+            }
+        }
+
+        Ok(Notify::new(
+            job_id,
+            PrevHash::new(prev_hash),
+            CoinBase1::new(parts.coin_base_1),
+            CoinBase2::new(parts.coin_base_2),
+            MerkleBranch::new(branch),
+            Version::new(template.version),
+            Bits::new(bits),
+            Time::new(template.curtime),
+            clean_jobs,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hash_of(byte: u8) -> DHash {
+        DHash::hash(&[byte])
+    }
+
+    #[test]
+    fn merkle_branch_of_no_extra_transactions_is_empty() {
+        assert!(merkle_branch(&[]).is_empty());
+    }
+
+    #[test]
+    fn merkle_branch_matches_hand_computed_two_transactions() {
+        let tx0 = hash_of(0);
+        let tx1 = hash_of(1);
+
+        let branch = merkle_branch(&[tx0, tx1]);
+
+        // level 0: row = [placeholder, tx0, tx1] -> odd, duplicate tx1 -> branch.push(tx0)
+        // level 1: row = [placeholder, dsha256(tx1 || tx1)] -> branch.push(dsha256(tx1||tx1))
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&tx1.into_inner());
+        buf.extend_from_slice(&tx1.into_inner());
+        let expected_second = DHash::hash(&buf);
+
+        assert_eq!(branch.len(), 2);
+        assert_eq!(branch[0].0, tx0.into_inner().to_vec());
+        assert_eq!(branch[1].0, expected_second.into_inner().to_vec());
+    }
+
+    #[test]
+    fn clean_jobs_evicts_all_previously_stored_job_contexts() {
+        let mut builder = JobBuilder::new(vec![0xaa, 0xbb], 4, vec![0x76, 0xa9]);
+
+        let template_a = GetBlockTemplateResult {
+            version: 1,
+            previous_block_hash: "00".repeat(32),
+            transactions: vec![],
+            coinbase_value: 5_000_000_000,
+            bits: "1d00ffff".to_string(),
+            height: 1,
+            curtime: 1_600_000_000,
+            default_witnesscommitment: None,
+        };
+        builder.build_notify(&template_a).unwrap();
+        assert_eq!(builder.jobs.len(), 1);
+
+        let mut template_b = template_a.clone();
+        template_b.previous_block_hash = "11".repeat(32);
+        builder.build_notify(&template_b).unwrap();
+
+        assert_eq!(
+            builder.jobs.len(),
+            1,
+            "job contexts from the previous chain tip must be evicted on clean_jobs"
+        );
+    }
+
+    #[test]
+    fn outstanding_job_contexts_are_capped() {
+        let mut builder = JobBuilder::new(vec![0xaa, 0xbb], 4, vec![0x76, 0xa9]);
+        let template = GetBlockTemplateResult {
+            version: 1,
+            previous_block_hash: "00".repeat(32),
+            transactions: vec![],
+            coinbase_value: 5_000_000_000,
+            bits: "1d00ffff".to_string(),
+            height: 1,
+            curtime: 1_600_000_000,
+            default_witnesscommitment: None,
+        };
+
+        for _ in 0..(MAX_OUTSTANDING_JOBS + 5) {
+            builder.build_notify(&template).unwrap();
+        }
+
+        assert_eq!(builder.jobs.len(), MAX_OUTSTANDING_JOBS);
+        assert_eq!(builder.job_order.len(), MAX_OUTSTANDING_JOBS);
+    }
+
+    fn submit_with_version(job_id: &[u8], version: u32) -> Submit {
+        Submit::new(
+            UserName::new("worker.1".to_string()),
+            JobId::from_slice(job_id),
+            ExtraNonce2::new(vec![0; 4]),
+            Time::new(1_600_000_000),
+            Nonce::new(0),
+            Version::new(version),
+        )
+    }
+
+    #[test]
+    fn validate_submit_accepts_a_roll_within_the_negotiated_mask() {
+        let mut builder = JobBuilder::new(vec![0xaa, 0xbb], 4, vec![0x76, 0xa9]);
+        builder.set_version_rolling_mask(Some(0x1fff_e000));
+        let template = GetBlockTemplateResult {
+            version: 0x2000_0000,
+            previous_block_hash: "00".repeat(32),
+            transactions: vec![],
+            coinbase_value: 5_000_000_000,
+            bits: "1d00ffff".to_string(),
+            height: 1,
+            curtime: 1_600_000_000,
+            default_witnesscommitment: None,
+        };
+        let notify = builder.build_notify(&template).unwrap();
+
+        let submit = submit_with_version(notify.job_id(), 0x2000_2000);
+        assert!(builder.validate_submit(&submit).is_ok());
+    }
+
+    #[test]
+    fn validate_submit_rejects_a_roll_outside_the_negotiated_mask() {
+        let mut builder = JobBuilder::new(vec![0xaa, 0xbb], 4, vec![0x76, 0xa9]);
+        builder.set_version_rolling_mask(Some(0x1fff_e000));
+        let template = GetBlockTemplateResult {
+            version: 0x2000_0000,
+            previous_block_hash: "00".repeat(32),
+            transactions: vec![],
+            coinbase_value: 5_000_000_000,
+            bits: "1d00ffff".to_string(),
+            height: 1,
+            curtime: 1_600_000_000,
+            default_witnesscommitment: None,
+        };
+        let notify = builder.build_notify(&template).unwrap();
+
+        // flips a bit outside the negotiated mask
+        let submit = submit_with_version(notify.job_id(), 0x2000_0001);
+        assert!(builder.validate_submit(&submit).is_err());
+    }
+
+    #[test]
+    fn validate_submit_rejects_an_unknown_job_id() {
+        let builder = JobBuilder::new(vec![0xaa, 0xbb], 4, vec![0x76, 0xa9]);
+        let submit = submit_with_version(b"not-a-real-job-id", 0x2000_0000);
+        assert!(builder.validate_submit(&submit).is_err());
+    }
+
+    use crate::v1::messages::rpc::json::GetBlockTemplateResultTransaction;
+
+    fn base_template() -> GetBlockTemplateResult {
+        GetBlockTemplateResult {
+            version: 1,
+            previous_block_hash: "00".repeat(32),
+            transactions: vec![],
+            coinbase_value: 5_000_000_000,
+            bits: "1d00ffff".to_string(),
+            height: 1,
+            curtime: 1_600_000_000,
+            default_witnesscommitment: None,
+        }
+    }
+
+    #[test]
+    fn build_notify_folds_the_merkle_branch_from_txid_not_a_hash_of_the_raw_data() {
+        // `data` stands in for a segwit transaction's BIP144 (witness) serialization: its
+        // dsha256 is the wtxid, which must NOT end up in the merkle branch. `txid` is the
+        // value that must be used instead, and deliberately does not match dsha256(data).
+        let data = hex::encode([0xaau8; 10]);
+        let txid = hash_of(0xbb);
+        let mut txid_bytes = txid.into_inner().to_vec();
+        txid_bytes.reverse(); // getblocktemplate reports txid in big-endian display order
+
+        let mut template = base_template();
+        template.transactions = vec![GetBlockTemplateResultTransaction {
+            data,
+            txid: hex::encode(txid_bytes),
+        }];
+
+        let mut builder = JobBuilder::new(vec![0xaa, 0xbb], 4, vec![0x76, 0xa9]);
+        let notify = builder.build_notify(&template).unwrap();
+
+        let expected = merkle_branch(&[txid]);
+        let actual = notify.merkle_branch();
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert_eq!(a.0, e.0);
+        }
+    }
+
+    #[test]
+    fn build_notify_rejects_a_malformed_previous_block_hash() {
+        let mut template = base_template();
+        template.previous_block_hash = "not-hex".to_string();
+
+        let mut builder = JobBuilder::new(vec![0xaa, 0xbb], 4, vec![0x76, 0xa9]);
+        assert!(builder.build_notify(&template).is_err());
+    }
+
+    #[test]
+    fn build_notify_rejects_malformed_bits() {
+        let mut template = base_template();
+        template.bits = "zz".to_string();
+
+        let mut builder = JobBuilder::new(vec![0xaa, 0xbb], 4, vec![0x76, 0xa9]);
+        assert!(builder.build_notify(&template).is_err());
+    }
+
+    #[test]
+    fn build_notify_rejects_a_malformed_transaction() {
+        let mut template = base_template();
+        template.transactions = vec![GetBlockTemplateResultTransaction {
+            data: "not-hex".to_string(),
+            txid: "00".repeat(32),
+        }];
+
+        let mut builder = JobBuilder::new(vec![0xaa, 0xbb], 4, vec![0x76, 0xa9]);
+        assert!(builder.build_notify(&template).is_err());
+    }
+
+    #[test]
+    fn build_notify_adds_a_witness_commitment_output_when_the_template_provides_one() {
+        let mut template = base_template();
+        template.default_witnesscommitment = Some(hex::encode([0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed]));
+
+        let mut builder = JobBuilder::new(vec![0xaa, 0xbb], 4, vec![0x76, 0xa9]);
+        builder.build_notify(&template).unwrap();
+
+        let job_context = builder.jobs.values().next().unwrap();
+        assert!(job_context.has_witness_commitment);
+        // segwit marker + flag immediately follow the 4-byte version field
+        assert_eq!(&job_context.coin_base_1[4..6], &[0x00, 0x01]);
+    }
+}
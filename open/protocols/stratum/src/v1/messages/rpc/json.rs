@@ -0,0 +1,61 @@
+//! Typed request/response structures for the subset of Bitcoin Core's JSON-RPC API that the
+//! job builder needs, following the same field naming as the `bitcoincore-rpc-json` crate so
+//! that the mapping to the RPC wire format stays obvious.
+
+use serde::{Deserialize, Serialize};
+
+/// A single transaction entry inside a `getblocktemplate` result
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct GetBlockTemplateResultTransaction {
+    /// Raw transaction, serialized as hex
+    pub data: String,
+    /// Transaction id
+    pub txid: String,
+}
+
+/// Result of the `getblocktemplate` RPC call, trimmed down to the fields needed to build a
+/// Stratum V1 `Notify`
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct GetBlockTemplateResult {
+    /// Block version
+    pub version: u32,
+    /// Hash of the previous block, big-endian as returned by Bitcoin Core
+    #[serde(rename = "previousblockhash")]
+    pub previous_block_hash: String,
+    /// Non-coinbase transactions to include in the block, in the order they must appear
+    pub transactions: Vec<GetBlockTemplateResultTransaction>,
+    /// Total value of the coinbase output, in satoshi
+    #[serde(rename = "coinbasevalue")]
+    pub coinbase_value: u64,
+    /// Compressed difficulty target
+    pub bits: String,
+    /// Block height of the template
+    pub height: u64,
+    /// Current time as seen by Bitcoin Core, suitable for the block header's `ntime`
+    pub curtime: u32,
+    /// `scriptPubKey` (hex) of the BIP141 witness commitment output Bitcoin Core expects the
+    /// coinbase to carry, computed from the witness merkle root and an all-zero witness
+    /// reserved value. Only present when the template contains at least one segwit
+    /// transaction.
+    #[serde(default)]
+    pub default_witnesscommitment: Option<String>,
+}
+
+/// Request parameters for the `submitblock` RPC call: a single hex encoded block
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct SubmitBlockRequest(pub String);
+
+/// Result of the `submitblock` RPC call: `None` on acceptance, otherwise a short rejection
+/// reason (e.g. `"duplicate"`, `"inconclusive"`, `"bad-txns-nonfinal"`)
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct SubmitBlockResult(pub Option<String>);
+
+impl SubmitBlockResult {
+    pub fn is_accepted(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn reject_reason(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
@@ -0,0 +1,27 @@
+//! Bridges Bitcoin Core's `getblocktemplate`/`submitblock` JSON-RPC calls to Stratum V1
+//! `Notify`/`Submit` messages, so that this crate can generate and validate mining jobs
+//! without depending on an upstream pool.
+
+pub mod block;
+pub mod coinbase;
+pub mod job;
+pub mod json;
+
+pub use block::{reconstruct_block, submit_block_request, BlockSubmitOutcome};
+pub use job::JobBuilder;
+
+/// Encodes `value` as a Bitcoin varint, shared by the coinbase and block builders
+pub(super) fn push_var_int(buf: &mut Vec<u8>, value: u64) {
+    if value < 0xfd {
+        buf.push(value as u8);
+    } else if value <= 0xffff {
+        buf.push(0xfd);
+        buf.extend_from_slice(&(value as u16).to_le_bytes());
+    } else if value <= 0xffff_ffff {
+        buf.push(0xfe);
+        buf.extend_from_slice(&(value as u32).to_le_bytes());
+    } else {
+        buf.push(0xff);
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+}
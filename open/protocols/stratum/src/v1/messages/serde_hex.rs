@@ -0,0 +1,186 @@
+//! Fixed-length, endianness-aware hex (de)serialization, in the style of
+//! `bitcoincore-rpc-json`'s `serde_hex` module. It takes the place of `HexU32Le` and of
+//! `HexBytes` for the fixed-length fields (`PrevHash` and the 32-bit header fields); the
+//! variable-length fields (job id, coinbase parts, merkle branch, extranonce 2) still use
+//! `HexBytes`. Unlike those wrappers, a malformed wire value (wrong length, non-hex
+//! characters) is rejected right at deserialization time instead of surfacing as a
+//! confusing panic or silent truncation further down the pipeline.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{Error as DeError, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Byte order applied to the wire representation of a `FixedHex` value
+pub trait ByteOrder: Clone + Copy + fmt::Debug {
+    /// Reorders `bytes` from in-memory order to wire order (and back, since the operation
+    /// is its own inverse for both orders supported here)
+    fn reorder(bytes: &mut [u8]);
+}
+
+/// Hex digits appear in the same order as the underlying bytes (used for hashes)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BigEndian;
+
+impl ByteOrder for BigEndian {
+    fn reorder(_bytes: &mut [u8]) {}
+}
+
+/// Hex digits appear byte-reversed relative to the underlying value (used for the 32-bit
+/// integer fields of Stratum V1, e.g. `nonce`/`version`/`bits`/`time`)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LittleEndian;
+
+impl ByteOrder for LittleEndian {
+    fn reorder(bytes: &mut [u8]) {
+        bytes.reverse();
+    }
+}
+
+/// A hex encoded, fixed-length byte string with a statically known size and byte order.
+/// `PREFIX` controls whether the wire representation carries a leading `0x`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixedHex<O, const N: usize, const PREFIX: bool = false> {
+    bytes: [u8; N],
+    _order: PhantomData<O>,
+}
+
+impl<O: ByteOrder, const N: usize, const PREFIX: bool> FixedHex<O, N, PREFIX> {
+    pub fn new(bytes: [u8; N]) -> Self {
+        Self {
+            bytes,
+            _order: PhantomData,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn to_wire_hex(&self) -> String {
+        let mut wire_bytes = self.bytes;
+        O::reorder(&mut wire_bytes);
+        let mut s = String::with_capacity(2 * N + if PREFIX { 2 } else { 0 });
+        if PREFIX {
+            s.push_str("0x");
+        }
+        for byte in &wire_bytes {
+            s.push_str(&format!("{:02x}", byte));
+        }
+        s
+    }
+
+    fn from_wire_hex(s: &str) -> Result<Self, String> {
+        let digits = if PREFIX {
+            s.strip_prefix("0x")
+                .ok_or_else(|| format!("hex value {} is missing the required 0x prefix", s))?
+        } else {
+            s
+        };
+
+        if digits.len() != 2 * N {
+            return Err(format!(
+                "expected {} hex digits ({} bytes), got {}",
+                2 * N,
+                N,
+                digits.len()
+            ));
+        }
+
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("invalid hex digit in {}", s));
+        }
+
+        let mut bytes = [0u8; N];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&digits[2 * i..2 * i + 2], 16)
+                .map_err(|e| format!("invalid hex digit in {}: {}", s, e))?;
+        }
+        O::reorder(&mut bytes);
+
+        Ok(Self::new(bytes))
+    }
+}
+
+impl<O: ByteOrder, const N: usize, const PREFIX: bool> Serialize for FixedHex<O, N, PREFIX> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_wire_hex())
+    }
+}
+
+impl<'de, O: ByteOrder, const N: usize, const PREFIX: bool> Deserialize<'de> for FixedHex<O, N, PREFIX> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct HexVisitor<O, const N: usize, const PREFIX: bool>(PhantomData<O>);
+
+        impl<'de, O: ByteOrder, const N: usize, const PREFIX: bool> Visitor<'de> for HexVisitor<O, N, PREFIX> {
+            type Value = FixedHex<O, N, PREFIX>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a {}-byte hex encoded string", N)
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: DeError,
+            {
+                FixedHex::from_wire_hex(v).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(HexVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn big_endian_round_trips_without_reordering() {
+        let value: FixedHex<BigEndian, 4> = FixedHex::new([0x01, 0x02, 0x03, 0x04]);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, "\"01020304\"");
+
+        let parsed: FixedHex<BigEndian, 4> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, value);
+    }
+
+    #[test]
+    fn little_endian_reverses_bytes_on_the_wire_but_not_in_memory() {
+        let value: FixedHex<LittleEndian, 4> = FixedHex::new([0x01, 0x02, 0x03, 0x04]);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, "\"04030201\"");
+
+        let parsed: FixedHex<LittleEndian, 4> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.as_bytes(), value.as_bytes());
+    }
+
+    #[test]
+    fn rejects_wrong_length_input() {
+        let result: Result<FixedHex<BigEndian, 4>, _> = serde_json::from_str("\"0102\"");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn rejects_non_hex_characters() {
+        let result: Result<FixedHex<BigEndian, 4>, _> = serde_json::from_str("\"zzzzzzzz\"");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn optional_0x_prefix_is_required_when_enabled() {
+        let value: FixedHex<BigEndian, 2, true> = FixedHex::new([0xab, 0xcd]);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, "\"0xabcd\"");
+
+        let missing_prefix: Result<FixedHex<BigEndian, 2, true>, _> = serde_json::from_str("\"abcd\"");
+        assert!(missing_prefix.is_err());
+    }
+}